@@ -1,11 +1,15 @@
-use std::{collections::HashMap, sync::Mutex};
-use rusqlite::Connection;
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{ser::Serializer, Serialize, Deserialize};
 use serde_json::{Value as JsonValue};
-use sqlite::{connect, execute as sqlite_execute, select as sqlite_select, batch as sqlite_batch};
+use sqlite::{
+    connect, execute as sqlite_execute, select as sqlite_select, batch as sqlite_batch,
+    backup as sqlite_backup, blob_read as sqlite_blob_read, blob_write as sqlite_blob_write,
+    ChangeRecord, ConnectionPool, PoolConfig,
+};
 use tauri::{
   plugin::{Builder, TauriPlugin},
-  Manager, 
+  Manager,
   AppHandle, Runtime, State,
 };
 mod sqlite;
@@ -18,6 +22,10 @@ enum Error {
     Rusqlite(rusqlite::Error),
     #[error("Not connected to {0}")]
     NotConnected(String),
+    #[error("Invalid base64 data: {0}")]
+    InvalidBase64(base64::DecodeError),
+    #[error("Background database task panicked: {0}")]
+    TaskFailed(String),
 }
 
 // Pass error through API
@@ -34,88 +42,159 @@ type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Default)]
 struct DbInstances (
-    Mutex<HashMap<String, Connection>>
+    Mutex<HashMap<String, Arc<ConnectionPool>>>
 );
 
 #[derive(Deserialize)]
 struct OpenOptions {
-    disable_foreign_keys: Option<bool>
-}
-
-fn open_db(state: State<'_, DbInstances>, db_path: String, options: OpenOptions) -> Result<bool> {
-    match connect(&db_path) {
-        Ok(mut conn) => {
-            if let Some(disable_foreign_keys) = options.disable_foreign_keys {
-                if disable_foreign_keys == true {
-                    _ = sqlite_execute(&mut conn, "PRAGMA foreign_keys = 0", vec!());
-                }
-            }
-            state.0.lock().unwrap().insert(db_path, conn);
-            Ok(true)
-        }    
-        Err(e) => Err(Error::Rusqlite(e))
-    }
+    disable_foreign_keys: Option<bool>,
+    max_connections: Option<usize>,
+    busy_timeout_ms: Option<u64>,
+    max_retries: Option<u32>,
+    enable_functions: Option<Vec<String>>,
+    extensions: Option<Vec<String>>,
+    watch: Option<bool>,
+}
+
+#[derive(Serialize, Clone)]
+struct ChangesPayload {
+    db_path: String,
+    changes: Vec<ChangeRecord>,
+}
+
+#[derive(Deserialize)]
+struct BackupOptions {
+    pages_per_step: Option<i32>,
+    sleep_ms: Option<u64>,
+}
+
+#[derive(Serialize, Clone)]
+struct BackupProgress {
+    remaining: i32,
+    pagecount: i32,
+}
+
+async fn open_db<R: Runtime>(app: AppHandle<R>, state: State<'_, DbInstances>, db_path: String, options: OpenOptions) -> Result<bool> {
+    let on_change = if options.watch.unwrap_or(false) {
+        let emitted_db_path = db_path.clone();
+        Some(Arc::new(move |changes: Vec<ChangeRecord>| {
+            let _ = app.emit_all("taurusqlite://changes", ChangesPayload {
+                db_path: emitted_db_path.clone(),
+                changes,
+            });
+        }) as sqlite::ChangeEmitter)
+    } else {
+        None
+    };
+    let config = PoolConfig {
+        disable_foreign_keys: options.disable_foreign_keys.unwrap_or(false),
+        busy_timeout_ms: options.busy_timeout_ms.unwrap_or(5000),
+        enable_functions: options.enable_functions.unwrap_or_default(),
+        extensions: options.extensions.unwrap_or_default(),
+        on_change,
+    };
+    let max_connections = options.max_connections.unwrap_or(8);
+    let max_retries = options.max_retries.unwrap_or(5);
+    let open_path = db_path.clone();
+    let pool = run_blocking(move || ConnectionPool::open(&open_path, config, max_connections, max_retries)).await?;
+    state.0.lock().unwrap().insert(db_path, Arc::new(pool));
+    Ok(true)
 }
 
 #[tauri::command]
 async fn load<R: Runtime>(app: AppHandle<R>, state: State<'_, DbInstances>, options: OpenOptions) -> Result<String> {
     let app_dir = app.path_resolver().app_data_dir().expect("Failed to resolve app_dir");
     let db_path = app_dir.join(STORE_FILENAME).as_path().display().to_string();
-    match open_db(state, db_path.clone(), options) {
+    match open_db(app, state, db_path.clone(), options).await {
         Ok(_) => Ok(db_path),
         Err(e) => Err(e)
     }
 }
 
 #[tauri::command]
-async fn open(state: State<'_, DbInstances>, db_path: String, options: OpenOptions) -> Result<bool> {
-    open_db(state, db_path, options)
+async fn open<R: Runtime>(app: AppHandle<R>, state: State<'_, DbInstances>, db_path: String, options: OpenOptions) -> Result<bool> {
+    open_db(app, state, db_path, options).await
+}
+
+/// Clone the pool handle for `db_path` and release the instances lock before touching SQLite,
+/// so concurrent commands against the same or different databases don't serialize on it.
+fn get_pool(state: &State<'_, DbInstances>, db_path: &str) -> Result<Arc<ConnectionPool>> {
+    state.0.lock().unwrap().get(db_path).cloned().ok_or(Error::NotConnected(db_path.to_string()))
+}
+
+/// Run a blocking SQLite operation on the blocking-task pool instead of the async worker thread.
+/// Retry backoffs and multi-step backups call `std::thread::sleep` under the hood, so running
+/// them inline here would tie up the runtime worker for the whole command and stall unrelated
+/// commands; `spawn_blocking` moves that waiting off onto a thread meant for it.
+async fn run_blocking<T, F>(task: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> rusqlite::Result<T> + Send + 'static,
+{
+    match tauri::async_runtime::spawn_blocking(task).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(Error::Rusqlite(e)),
+        Err(e) => Err(Error::TaskFailed(e.to_string())),
+    }
 }
 
 #[tauri::command]
 async fn set_pragma(state: State<'_, DbInstances>, db_path: String, key: String, value: JsonValue) -> Result<bool> {
-    let mut mutex_map = state.0.lock().unwrap();
-    let mut conn = mutex_map.get_mut(&db_path).ok_or(Error::NotConnected(db_path))?;
-    match sqlite_execute(&mut conn, format!("PRAGMA {} = {}", key, value).as_str(), vec!()) {
-        Ok(result) => Ok(result),
-        Err(e) => Err(Error::Rusqlite(e))
-    }
+    let pool = get_pool(&state, &db_path)?;
+    run_blocking(move || sqlite_execute(&pool, format!("PRAGMA {} = {}", key, value).as_str(), vec!())).await
 }
 
 #[tauri::command]
 async fn select(state: State<'_, DbInstances>, db_path: String, query: String, params: Vec<JsonValue>) -> Result<Vec<HashMap<String, JsonValue>>> {
-    let mut mutex_map = state.0.lock().unwrap();
-    let conn = mutex_map.get_mut(&db_path).ok_or(Error::NotConnected(db_path))?;
-    match sqlite_select(&conn, query.as_str(), params) {
-        Ok(result) => Ok(result),
-        Err(e) => Err(Error::Rusqlite(e))
-    }
+    let pool = get_pool(&state, &db_path)?;
+    run_blocking(move || sqlite_select(&pool, query.as_str(), params)).await
 }
 
 #[tauri::command]
 async fn execute(state: State<'_, DbInstances>, db_path: String, query: String, params: Vec<JsonValue>) -> Result<bool> {
-    let mut mutex_map = state.0.lock().unwrap();
-    let mut conn = mutex_map.get_mut(&db_path).ok_or(Error::NotConnected(db_path))?;
-    match sqlite_execute(&mut conn, query.as_str(), params) {
-        Ok(result) => Ok(result),
-        Err(e) => Err(Error::Rusqlite(e))
-    }
+    let pool = get_pool(&state, &db_path)?;
+    run_blocking(move || sqlite_execute(&pool, query.as_str(), params)).await
 }
 
 #[tauri::command]
-async fn batch(state: State<'_, DbInstances>, db_path: String, queries: Vec<(&str, Vec<JsonValue>)>) -> Result<bool> {
-    let mut mutex_map = state.0.lock().unwrap();
-    let mut conn = mutex_map.get_mut(&db_path).ok_or(Error::NotConnected(db_path))?;
-    match sqlite_batch(&mut conn, queries) {
-        Ok(result) => Ok(result),
-        Err(e) => Err(Error::Rusqlite(e))
-    }
+async fn batch(state: State<'_, DbInstances>, db_path: String, queries: Vec<(String, Vec<JsonValue>)>) -> Result<bool> {
+    let pool = get_pool(&state, &db_path)?;
+    run_blocking(move || sqlite_batch(&pool, queries)).await
+}
+
+#[tauri::command]
+async fn backup<R: Runtime>(app: AppHandle<R>, state: State<'_, DbInstances>, db_path: String, dest_path: String, options: BackupOptions) -> Result<bool> {
+    let pages_per_step = options.pages_per_step.unwrap_or(100);
+    let sleep_ms = options.sleep_ms.unwrap_or(5);
+    let mut dest_conn = connect(&dest_path).map_err(Error::Rusqlite)?;
+    let pool = get_pool(&state, &db_path)?;
+    run_blocking(move || {
+        sqlite_backup(&pool, &mut dest_conn, pages_per_step, sleep_ms, |progress| {
+            let _ = app.emit_all("taurusqlite://backup-progress", BackupProgress {
+                remaining: progress.remaining,
+                pagecount: progress.pagecount,
+            });
+        })
+    }).await.map(|()| true)
+}
+
+#[tauri::command]
+async fn blob_read(state: State<'_, DbInstances>, db_path: String, table: String, column: String, rowid: i64, offset: u64, length: usize) -> Result<String> {
+    let pool = get_pool(&state, &db_path)?;
+    run_blocking(move || sqlite_blob_read(&pool, &table, &column, rowid, offset, length)).await.map(|bytes| BASE64.encode(bytes))
+}
+
+#[tauri::command]
+async fn blob_write(state: State<'_, DbInstances>, db_path: String, table: String, column: String, rowid: i64, offset: u64, data: String) -> Result<bool> {
+    let bytes = BASE64.decode(data).map_err(Error::InvalidBase64)?;
+    let pool = get_pool(&state, &db_path)?;
+    run_blocking(move || sqlite_blob_write(&pool, &table, &column, rowid, offset, &bytes)).await
 }
 
 /// Initializes the plugin.
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
   Builder::new("taurusqlite")
-    .invoke_handler(tauri::generate_handler![open, select, execute, set_pragma, batch, load])
+    .invoke_handler(tauri::generate_handler![open, select, execute, set_pragma, batch, load, backup, blob_read, blob_write])
     .setup(|app| {
         app.manage(DbInstances::default());
         Ok(())
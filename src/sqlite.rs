@@ -0,0 +1,565 @@
+//! Thin wrapper around rusqlite used by the plugin commands.
+//!
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+    thread::sleep,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use regex::Regex;
+use rusqlite::{
+    backup::{Backup, Progress},
+    functions::FunctionFlags,
+    hooks::Action,
+    params_from_iter,
+    types::{Value, ValueRef},
+    Connection, DatabaseName, ErrorCode, Params, Row, Statement,
+};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+/// Prefix tagging a JSON string as base64-encoded binary data, as opposed to plain text.
+const BASE64_TAG: &str = "base64:";
+
+/// Open connection on SQLite database
+pub(crate) fn connect(path_to_db: &str) -> rusqlite::Result<Connection> {
+    Connection::open(Path::new(path_to_db))
+}
+
+/// Per-connection setup applied to every connection a [`ConnectionPool`] opens, so it is
+/// consistently in effect no matter which pooled connection ends up serving a query.
+#[derive(Clone, Default)]
+pub(crate) struct PoolConfig {
+    pub(crate) disable_foreign_keys: bool,
+    pub(crate) busy_timeout_ms: u64,
+    pub(crate) enable_functions: Vec<String>,
+    pub(crate) extensions: Vec<String>,
+    pub(crate) on_change: Option<ChangeEmitter>,
+}
+
+/// A single row-level change captured by the update hook.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ChangeRecord {
+    pub(crate) table: String,
+    pub(crate) action: String,
+    pub(crate) rowid: i64,
+}
+
+/// Callback a [`ConnectionPool`] invokes with the batch of changes accumulated over one
+/// transaction, once it commits. Type-erased behind an `Arc` so `sqlite.rs` doesn't need to know about `AppHandle`.
+pub(crate) type ChangeEmitter = Arc<dyn Fn(Vec<ChangeRecord>) + Send + Sync>;
+
+/// Register the update/commit/rollback hooks that turn row-level changes into a single batched
+/// call to `on_change` per committed transaction. Hook callbacks can't call back into `conn`, so
+/// the update hook only appends to a buffer that the commit hook drains and hands off; the
+/// rollback hook clears that buffer so a rolled-back transaction's changes are never emitted.
+fn watch_changes(conn: &Connection, on_change: ChangeEmitter) {
+    let buffer: Arc<Mutex<Vec<ChangeRecord>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let update_buffer = Arc::clone(&buffer);
+    conn.update_hook(Some(move |action: Action, _db: &str, table: &str, rowid: i64| {
+        update_buffer.lock().unwrap().push(ChangeRecord {
+            table: table.to_string(),
+            action: match action {
+                Action::SQLITE_INSERT => "INSERT".to_string(),
+                Action::SQLITE_UPDATE => "UPDATE".to_string(),
+                Action::SQLITE_DELETE => "DELETE".to_string(),
+                other => format!("{:?}", other),
+            },
+            rowid,
+        });
+    }));
+
+    let rollback_buffer = Arc::clone(&buffer);
+    conn.rollback_hook(Some(move || {
+        rollback_buffer.lock().unwrap().clear();
+    }));
+
+    conn.commit_hook(Some(move || {
+        let changes = std::mem::take(&mut *buffer.lock().unwrap());
+        if !changes.is_empty() {
+            on_change(changes);
+        }
+        false
+    }));
+}
+
+/// Load each shared library in `extensions` (FTS5 tokenizers, `sqlite-vec`, custom collations...)
+/// into `conn`, re-disabling extension loading afterwards so it isn't left enabled at runtime.
+fn load_extensions(conn: &Connection, extensions: &[String]) -> rusqlite::Result<()> {
+    if extensions.is_empty() {
+        return Ok(());
+    }
+    conn.load_extension_enable()?;
+    for path in extensions {
+        let result = unsafe { conn.load_extension(path, None) };
+        if let Err(e) = result {
+            conn.load_extension_disable()?;
+            return Err(e);
+        }
+    }
+    conn.load_extension_disable()
+}
+
+/// Error type used to box whatever went wrong while building a function's cached aux data.
+type AuxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Register the subset of `names` this plugin knows about as SQL scalar functions on `conn`.
+/// Unknown names are ignored so a typo in `enable_functions` doesn't fail `open`/`load`.
+fn register_functions(conn: &Connection, names: &[String]) -> rusqlite::Result<()> {
+    for name in names {
+        match name.to_uppercase().as_str() {
+            "REGEXP" => register_regexp(conn)?,
+            "LOWER_UNICODE" => register_lower_unicode(conn)?,
+            "UUID" => register_uuid(conn)?,
+            "JSON_VALID" => register_json_valid(conn)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `REGEXP(pattern, text)`: true when `text` matches `pattern`. Compiled patterns are cached
+/// in the function call's aux data, keyed by the pattern argument, so repeated calls with the
+/// same pattern (e.g. `WHERE name REGEXP ?1` over many rows) don't recompile it every row.
+fn register_regexp(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "REGEXP",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let pattern: Arc<Regex> = ctx.get_or_create_aux(0, |vr| -> Result<_, AuxError> {
+                Ok(Regex::new(vr.as_str()?)?)
+            })?;
+            let text = ctx.get_raw(1).as_str().map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+            Ok(pattern.is_match(text))
+        },
+    )
+}
+
+/// `LOWER_UNICODE(text)`: unicode-aware lowercasing, unlike SQLite's built-in `LOWER` which
+/// only folds ASCII.
+fn register_lower_unicode(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "LOWER_UNICODE",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let text = ctx.get_raw(0).as_str().map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+            Ok(text.to_lowercase())
+        },
+    )
+}
+
+/// `UUID()`: a random (v4) UUID. Not deterministic, so it can't be used in an index.
+fn register_uuid(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("UUID", 0, FunctionFlags::SQLITE_UTF8, |_ctx| {
+        Ok(Uuid::new_v4().to_string())
+    })
+}
+
+/// `JSON_VALID(text)`: true when `text` parses as JSON.
+fn register_json_valid(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "JSON_VALID",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let text = ctx.get_raw(0).as_str().map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+            Ok(serde_json::from_str::<JsonValue>(text).is_ok())
+        },
+    )
+}
+
+/// A small bounded pool of connections sharing one SQLite page cache (`cache=shared`), so
+/// concurrent reads no longer serialize behind a single `Connection`.
+///
+/// The first connection is opened eagerly by [`open`](Self::open); further ones are opened lazily,
+/// up to `max_connections`, and all are handed out via [`checkout`](Self::checkout).
+/// Callers needing resilience against transient `SQLITE_BUSY`/`SQLITE_LOCKED` should go through
+/// [`with_retry`](Self::with_retry) instead of checking out a connection directly.
+pub(crate) struct ConnectionPool {
+    path: String,
+    config: PoolConfig,
+    max_connections: usize,
+    max_retries: u32,
+    idle: Mutex<Vec<Connection>>,
+    opened: Mutex<usize>,
+    available: Condvar,
+}
+
+impl ConnectionPool {
+    pub(crate) fn open(
+        path: &str,
+        config: PoolConfig,
+        max_connections: usize,
+        max_retries: u32,
+    ) -> rusqlite::Result<Self> {
+        let pool = ConnectionPool {
+            path: path.to_string(),
+            config,
+            max_connections: max_connections.max(1),
+            max_retries,
+            idle: Mutex::new(Vec::new()),
+            opened: Mutex::new(0),
+            available: Condvar::new(),
+        };
+        // Open one connection eagerly so a bad path/permission surfaces immediately.
+        let conn = pool.open_connection()?;
+        *pool.opened.lock().unwrap() = 1;
+        pool.idle.lock().unwrap().push(conn);
+        Ok(pool)
+    }
+
+    fn open_connection(&self) -> rusqlite::Result<Connection> {
+        let conn = Connection::open(format!("file:{}?cache=shared", self.path))?;
+        conn.busy_timeout(Duration::from_millis(self.config.busy_timeout_ms))?;
+        if self.config.disable_foreign_keys {
+            conn.execute("PRAGMA foreign_keys = 0", [])?;
+        }
+        register_functions(&conn, &self.config.enable_functions)?;
+        load_extensions(&conn, &self.config.extensions)?;
+        if let Some(on_change) = self.config.on_change.clone() {
+            watch_changes(&conn, on_change);
+        }
+        Ok(conn)
+    }
+
+    /// Hand out an idle connection, opening a new one if under `max_connections`, otherwise
+    /// blocking until one is released.
+    pub(crate) fn checkout(&self) -> rusqlite::Result<PooledConnection<'_>> {
+        let mut idle = self.idle.lock().unwrap();
+        loop {
+            if let Some(conn) = idle.pop() {
+                return Ok(PooledConnection { pool: self, conn: Some(conn) });
+            }
+            let mut opened = self.opened.lock().unwrap();
+            if *opened < self.max_connections {
+                *opened += 1;
+                drop(opened);
+                return match self.open_connection() {
+                    Ok(conn) => Ok(PooledConnection { pool: self, conn: Some(conn) }),
+                    Err(e) => {
+                        *self.opened.lock().unwrap() -= 1;
+                        self.available.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+            drop(opened);
+            idle = self.available.wait(idle).unwrap();
+        }
+    }
+
+    fn release(&self, conn: Connection) {
+        self.idle.lock().unwrap().push(conn);
+        self.available.notify_one();
+    }
+
+    /// Check out a connection and run `op` against it, retrying with a short backoff when
+    /// SQLite reports the database as busy or locked, exactly like a classic busy handler.
+    pub(crate) fn with_retry<T>(&self, mut op: impl FnMut(&mut Connection) -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+        let mut pooled = self.checkout()?;
+        let mut attempt = 0;
+        loop {
+            match op(&mut pooled) {
+                Ok(value) => return Ok(value),
+                Err(rusqlite::Error::SqliteFailure(e, _))
+                    if attempt < self.max_retries
+                        && matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) =>
+                {
+                    attempt += 1;
+                    sleep(Duration::from_millis(10 * attempt as u64));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// A connection checked out from a [`ConnectionPool`], returned to the pool's idle list on drop.
+pub(crate) struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    conn: Option<Connection>,
+}
+
+impl<'a> std::ops::Deref for PooledConnection<'a> {
+    type Target = Connection;
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl<'a> std::ops::DerefMut for PooledConnection<'a> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledConnection<'a> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.release(conn);
+        }
+    }
+}
+
+/// Prepare statement from SQL query
+fn prepare<'a>(conn: &'a Connection, query: &str) -> rusqlite::Result<Statement<'a>> {
+    conn.prepare(query)
+}
+
+/// Get columns name from statement
+fn get_columns_names(stmt: &Statement) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for name in stmt.column_names() {
+        result.push(name.to_string());
+    }
+    result
+}
+
+/// Parse column data from result
+fn parse_column_data(value_to_parse: ValueRef) -> JsonValue {
+    match value_to_parse {
+        ValueRef::Null => JsonValue::Null,
+        ValueRef::Integer(i) => json!(i),
+        ValueRef::Real(f) => json!(f),
+        ValueRef::Text(t) => json!(std::str::from_utf8(t).unwrap()),
+        ValueRef::Blob(b) => json!(b),
+    }
+}
+
+/// Parse row from result
+fn parse_row(names: &[String], row: &Row) -> HashMap<String, JsonValue> {
+    let mut parsed_row: HashMap<String, JsonValue> = HashMap::new();
+    for name in names.iter() {
+        match row.get_ref(name.as_str()) {
+            Ok(column_ref) => parsed_row.insert(name.to_owned(), parse_column_data(column_ref)),
+            Err(e) => panic!("{:?}", e),
+        };
+    }
+    parsed_row
+}
+
+/// Parse params from JSON format to Rusqlite
+fn parse_params(params: Vec<JsonValue>) -> Vec<Value> {
+    let mut parsed: Vec<Value> = Vec::new();
+    for p in params {
+        if p.is_null() {
+            parsed.push(Value::Null);
+        } else if p.is_string() {
+            parsed.push(parse_string_param(p.as_str().unwrap()));
+        } else if p.is_i64() {
+            parsed.push(Value::Integer(p.as_i64().unwrap()));
+        } else if p.is_u64() {
+            // Wider than i64::MAX (serde_json keeps these as an unsigned PosInt): there is no
+            // lossless SQLite representation, so truncate via the same bit pattern rather than
+            // silently dropping the parameter and shifting every later positional bind.
+            parsed.push(Value::Integer(p.as_u64().unwrap() as i64));
+        } else if p.is_f64() {
+            parsed.push(Value::Real(p.as_f64().unwrap()));
+        } else if p.is_boolean() {
+            parsed.push(Value::Integer(p.as_bool().unwrap() as i64));
+        } else if p.is_array() {
+            let bytes: Vec<u8> = p
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|b| b.as_u64().filter(|v| *v <= 255).map(|v| v as u8))
+                .collect();
+            parsed.push(Value::Blob(bytes));
+        }
+    }
+    parsed
+}
+
+/// Decode a string param, treating one tagged with [`BASE64_TAG`] as a blob instead of text.
+fn parse_string_param(s: &str) -> Value {
+    match s.strip_prefix(BASE64_TAG) {
+        Some(encoded) => match BASE64.decode(encoded) {
+            Ok(bytes) => Value::Blob(bytes),
+            Err(_) => Value::Text(s.to_string()),
+        },
+        None => Value::Text(s.to_string()),
+    }
+}
+
+/// Query the database
+fn select_query<P: Params>(
+    conn: &Connection,
+    query: &str,
+    params: P,
+) -> rusqlite::Result<Vec<HashMap<String, JsonValue>>> {
+    let mut result: Vec<HashMap<String, JsonValue>> = Vec::new();
+    let mut stmt = prepare(conn, query)?;
+    let names = get_columns_names(&stmt);
+    let mut rows = stmt.query(params)?;
+    while let Some(row) = rows.next()? {
+        result.push(parse_row(&names, row));
+    }
+    Ok(result)
+}
+
+/// Execute a query
+fn execute_query<P: Params>(conn: &Connection, query: &str, params: P) -> rusqlite::Result<bool> {
+    prepare(conn, query)?.execute(params)?;
+    Ok(true)
+}
+
+pub(crate) fn select(
+    pool: &ConnectionPool,
+    query: &str,
+    params: Vec<JsonValue>,
+) -> rusqlite::Result<Vec<HashMap<String, JsonValue>>> {
+    pool.with_retry(|conn| select_query(conn, query, params_from_iter(parse_params(params.clone()))))
+}
+
+pub(crate) fn execute(
+    pool: &ConnectionPool,
+    query: &str,
+    params: Vec<JsonValue>,
+) -> rusqlite::Result<bool> {
+    pool.with_retry(|conn| {
+        if params.len() > 0 && params.get(0).unwrap().is_array() {
+            let transaction = conn.transaction()?;
+            for p in &params {
+                transaction.execute(query, params_from_iter(parse_params(p.as_array().unwrap().to_owned())))?;
+            }
+            transaction.commit()?;
+            Ok(true)
+        } else {
+            execute_query(conn, query, params_from_iter(parse_params(params.clone())))
+        }
+    })
+}
+
+pub(crate) fn batch(pool: &ConnectionPool, queries: Vec<(String, Vec<JsonValue>)>) -> rusqlite::Result<bool> {
+    pool.with_retry(|conn| {
+        let transaction = conn.transaction()?;
+        for (query, params) in &queries {
+            transaction.execute(query, params_from_iter(parse_params(params.clone())))?;
+        }
+        transaction.commit()?;
+        Ok(true)
+    })
+}
+
+/// Wrap an I/O failure from a [`rusqlite::blob::Blob`] seek/read/write as a rusqlite error.
+fn blob_io_error(e: std::io::Error) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+}
+
+/// Read a byte range out of a column's incremental blob handle, without loading the whole blob.
+pub(crate) fn blob_read(
+    pool: &ConnectionPool,
+    table: &str,
+    column: &str,
+    rowid: i64,
+    offset: u64,
+    length: usize,
+) -> rusqlite::Result<Vec<u8>> {
+    pool.with_retry(|conn| {
+        let mut blob = conn.blob_open(DatabaseName::Main, table, column, rowid, true)?;
+        blob.seek(SeekFrom::Start(offset)).map_err(blob_io_error)?;
+        let mut buf = vec![0u8; length];
+        let read = blob.read(&mut buf).map_err(blob_io_error)?;
+        buf.truncate(read);
+        Ok(buf)
+    })
+}
+
+/// Write a byte range into a column's incremental blob handle, without loading the whole blob.
+pub(crate) fn blob_write(
+    pool: &ConnectionPool,
+    table: &str,
+    column: &str,
+    rowid: i64,
+    offset: u64,
+    data: &[u8],
+) -> rusqlite::Result<bool> {
+    pool.with_retry(|conn| {
+        let mut blob = conn.blob_open(DatabaseName::Main, table, column, rowid, false)?;
+        blob.seek(SeekFrom::Start(offset)).map_err(blob_io_error)?;
+        blob.write_all(data).map_err(blob_io_error)?;
+        Ok(true)
+    })
+}
+
+/// Copy `src` into `dest` using SQLite's online backup API, reporting progress after every step.
+pub(crate) fn backup<F: FnMut(Progress)>(
+    src: &ConnectionPool,
+    dest: &mut Connection,
+    pages_per_step: i32,
+    sleep_ms: u64,
+    mut on_progress: F,
+) -> rusqlite::Result<()> {
+    let src = src.checkout()?;
+    let backup = Backup::new(&*src, dest)?;
+    loop {
+        let step_result = backup.step(pages_per_step)?;
+        on_progress(backup.progress());
+        match step_result {
+            rusqlite::backup::StepResult::Done => break,
+            rusqlite::backup::StepResult::More => sleep(Duration::from_millis(sleep_ms)),
+            rusqlite::backup::StepResult::Busy | rusqlite::backup::StepResult::Locked => {
+                sleep(Duration::from_millis(sleep_ms))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db_path(label: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("taurusqlite_test_{}_{}.sqlite3", label, Uuid::new_v4()))
+            .display()
+            .to_string()
+    }
+
+    #[test]
+    fn checkout_blocks_past_max_connections_until_release() {
+        let pool = ConnectionPool::open(&temp_db_path("pool"), PoolConfig::default(), 1, 0).unwrap();
+        let first = pool.checkout().unwrap();
+
+        std::thread::scope(|scope| {
+            let handle = scope.spawn(|| pool.checkout().unwrap());
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!handle.is_finished(), "checkout should block while the only connection is held");
+
+            drop(first);
+            handle.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn rollback_discards_buffered_changes_before_next_commit() {
+        let emitted: Arc<Mutex<Vec<Vec<ChangeRecord>>>> = Arc::new(Mutex::new(Vec::new()));
+        let emitted_for_hook = Arc::clone(&emitted);
+        let on_change: ChangeEmitter = Arc::new(move |changes| {
+            emitted_for_hook.lock().unwrap().push(changes);
+        });
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE foo (id INTEGER PRIMARY KEY)", []).unwrap();
+        watch_changes(&conn, on_change);
+
+        conn.execute("BEGIN", []).unwrap();
+        conn.execute("INSERT INTO foo (id) VALUES (1)", []).unwrap();
+        conn.execute("ROLLBACK", []).unwrap();
+
+        conn.execute("INSERT INTO foo (id) VALUES (2)", []).unwrap();
+
+        let batches = emitted.lock().unwrap();
+        assert_eq!(batches.len(), 1, "the rolled-back insert must not be emitted");
+        assert_eq!(batches[0].len(), 1);
+        assert_eq!(batches[0][0].rowid, 2);
+    }
+}